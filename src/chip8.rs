@@ -1,3 +1,5 @@
+use rand::Rng;
+
 static CHIP8_FONT_SET: &[u8; 80] = &[
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
     0x20, 0x60, 0x20, 0x20, 0x70, // 1
@@ -17,7 +19,7 @@ static CHIP8_FONT_SET: &[u8; 80] = &[
     0xF0, 0x80, 0xF0, 0x80, 0x80  // F
 ];
 
-struct Chip8 {
+pub(crate) struct Chip8 {
     current_opcode: u16,
     /// The Chip-8 language is capable of accessing up to 4KB (4,096 bytes) of RAM, from location 0x000 (0) to 0xFFF (4095). The first 512 bytes, from 0x000 to 0x1FF, are where the original interpreter was located, and should not be used by programs.
     memory: [u8; 4096],
@@ -28,7 +30,7 @@ struct Chip8 {
     /// Used to store the currently executing address.
     program_counter: u16,
     /// The original implementation of the Chip-8 language used a 64x32-pixel monochrome display.
-    gfx: [u8; 64 * 32],
+    pub(crate) gfx: [u8; 64 * 32],
     /// The delay timer is active whenever the delay timer register (DT) is non-zero. This timer does nothing more than subtract 1 from the value of DT at a rate of 60Hz. When DT reaches 0, it deactivates.
     delay_timer: u8,
     /// The sound timer is active whenever the sound timer register (ST) is non-zero. This timer also decrements at a rate of 60Hz, however, as long as ST's value is greater than zero, the Chip-8 buzzer will sound. When ST reaches zero, the sound timer deactivates.
@@ -36,6 +38,68 @@ struct Chip8 {
     /// Used to point to the topmost level of the stack.
     stack_pointer: u16,
     stack: [u16; 16],
+    /// HEX based keypad (0x0-0xF), true when the corresponding key is currently pressed.
+    pub(crate) keys: [bool; 16],
+    /// Opcode semantics that differ between CHIP-8 and CHIP-48/SUPER-CHIP interpreters.
+    pub(crate) quirks: Quirks,
+}
+
+impl Default for Chip8 {
+    fn default() -> Chip8 {
+        let mut chip8 = Chip8 {
+            current_opcode: 0,
+            memory: [0; 4096],
+            v: [0; 16],
+            index_register: 0,
+            program_counter: 0,
+            gfx: [0; 64 * 32],
+            delay_timer: 0,
+            sound_timer: 0,
+            stack_pointer: 0,
+            stack: [0; 16],
+            keys: [false; 16],
+            quirks: Quirks::default(),
+        };
+        chip8.initialize();
+        chip8
+    }
+}
+
+/// Several CHIP-8 opcodes gained conflicting interpretations once the CHIP-48 and SUPER-CHIP
+/// interpreters shipped. These toggles pick which behavior `decode_opcode` follows so the
+/// broad corpus of quirk-sensitive test ROMs runs correctly on either lineage.
+#[derive(Clone, Copy)]
+pub(crate) struct Quirks {
+    /// `8xy6`/`8xyE` (SHR/SHL): shift `Vy` into `Vx` (classic CHIP-8) instead of shifting `Vx` in place (CHIP-48/SUPER-CHIP).
+    pub(crate) shift_uses_vy: bool,
+    /// `Fx55`/`Fx65` (register dump/load): leave `I` incremented by `x + 1` afterwards (classic CHIP-8).
+    pub(crate) load_store_increments_i: bool,
+    /// `Bnnn` (jump): add `Vx` -- the register named by `nnn`'s top nibble -- instead of `V0` (CHIP-48/SUPER-CHIP).
+    pub(crate) jump_with_vx: bool,
+}
+
+impl Quirks {
+    pub(crate) fn chip8() -> Quirks {
+        Quirks {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_with_vx: false,
+        }
+    }
+
+    pub(crate) fn super_chip() -> Quirks {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            jump_with_vx: true,
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Quirks {
+        Quirks::chip8()
+    }
 }
 
 const LAST_12_BITS_MASK: u16 = 0x0FFF;
@@ -48,7 +112,7 @@ const FOURTH_4_BITS_MASK: u16 = 0x000F;
 const CHIP8_PROGRAM_OFFSET: u16 = 0x200;
 const ETI660_PROGRAM_OFFSET: u16 = 0x600;
 
-enum ProgramKind {
+pub(crate) enum ProgramKind {
     CHIP8,
     ETI660
 }
@@ -87,6 +151,15 @@ impl Chip8 {
         }
     }
 
+    /// Runs a single fetch/decode/execute cycle.
+    pub(crate) fn step(&mut self) {
+        self.fetch_opcode();
+        // Advance to the next instruction up front; opcodes that jump, call, return, skip or
+        // block (Fx0A with no key down) adjust `program_counter` again from this base.
+        self.program_counter += 2;
+        self.decode_opcode();
+    }
+
     fn fetch_opcode(&mut self) {
         self.current_opcode = u16::from_be_bytes([self.memory[self.program_counter as usize], self.memory[(self.program_counter + 1) as usize]])
     }
@@ -147,7 +220,7 @@ impl Chip8 {
             }
             // ADD Vx, byte
             0x7000 => {
-                self.v[x as usize] += last_2_n;
+                self.v[x as usize] = self.v[x as usize].wrapping_add(last_2_n);
             }
             0x8000 => {
                 match fourth_1_n {
@@ -167,27 +240,29 @@ impl Chip8 {
                     },
                     // SUB Vx, Vy
                     0x5 => {
-                        let x_bigger = self.v[x as usize] > self.v[y as usize];
-                        self.v[F] = if x_bigger { 1 } else { 0 }
+                        let (diff, overflowing) = self.v[x as usize].overflowing_sub(self.v[y as usize]);
+                        self.v[x as usize] = diff;
+                        self.v[F] = if overflowing { 0 } else { 1 }
                     },
                     // SHR Vx {, Vy}
                     0x6 => {
-                        let x_lsb = self.v[x as usize] & 1;
-                        self.v[F] = if x_lsb == 1 { 1 } else { 0 };
-                        self.v[x as usize] >>= 1;
+                        let source = if self.quirks.shift_uses_vy { self.v[y as usize] } else { self.v[x as usize] };
+                        self.v[F] = source & 1;
+                        self.v[x as usize] = source >> 1;
                     },
                     // SUBN Vx, Vy
                     0x7 => {
-                        let y_bigger = self.v[y as usize] > self.v[x as usize];
-                        self.v[F] = if y_bigger { 1 } else { 0 }
+                        let (diff, overflowing) = self.v[y as usize].overflowing_sub(self.v[x as usize]);
+                        self.v[x as usize] = diff;
+                        self.v[F] = if overflowing { 0 } else { 1 }
                     },
                     // SHL Vx {, Vy}
                     0xE => {
-                        let x_msb = (self.v[x as usize] >> 7) & 1;
-                        self.v[F] = if x_msb == 1 { 1 } else { 0 };
-                        self.v[x as usize] <<= 1;
+                        let source = if self.quirks.shift_uses_vy { self.v[y as usize] } else { self.v[x as usize] };
+                        self.v[F] = (source >> 7) & 1;
+                        self.v[x as usize] = source << 1;
                     },
-                    _ => todo!()
+                    _ => eprint!("Invalid opcode 0x{:X}", self.current_opcode),
                 }
             }
             // SNE Vx, Vy
@@ -200,10 +275,107 @@ impl Chip8 {
             0xA000 => {
                 self.index_register = last_3_n;
             }
-            // JP V0, addr
+            // JP V0, addr (or JP Vx, addr under the jump_with_vx quirk)
             0xB000 => {
-                self.program_counter = last_3_n + self.v[0] as u16;
+                let register = if self.quirks.jump_with_vx { x as usize } else { 0 };
+                self.program_counter = last_3_n + self.v[register] as u16;
             }
+            // RND Vx, byte
+            0xC000 => {
+                let random_byte: u8 = rand::thread_rng().gen();
+                self.v[x as usize] = random_byte & last_2_n;
+            }
+            // DRW Vx, Vy, nibble
+            0xD000 => {
+                let vx = self.v[x as usize] as usize;
+                let vy = self.v[y as usize] as usize;
+                let height = fourth_1_n as usize;
+
+                self.v[F] = 0;
+
+                for row in 0..height {
+                    let sprite_byte = self.memory[(self.index_register as usize) + row];
+                    let py = (vy + row) % 32;
+
+                    for bit in 0..8 {
+                        let sprite_pixel = (sprite_byte >> (7 - bit)) & 1;
+                        if sprite_pixel == 0 {
+                            continue;
+                        }
+
+                        let px = (vx + bit) % 64;
+                        let gfx_index = py * 64 + px;
+
+                        if self.gfx[gfx_index] != 0 {
+                            self.v[F] = 1;
+                        }
+                        self.gfx[gfx_index] ^= 0xFF;
+                    }
+                }
+            }
+            0xE000 => match last_2_n {
+                // SKP Vx
+                0x9E => {
+                    let key = self.v[x as usize] as usize % self.keys.len();
+                    if self.keys[key] {
+                        self.program_counter += 2;
+                    }
+                },
+                // SKNP Vx
+                0xA1 => {
+                    let key = self.v[x as usize] as usize % self.keys.len();
+                    if !self.keys[key] {
+                        self.program_counter += 2;
+                    }
+                },
+                _ => eprint!("Invalid opcode 0x{:X}", self.current_opcode),
+            },
+            0xF000 => match last_2_n {
+                // LD Vx, K
+                0x0A => {
+                    match self.keys.iter().position(|&pressed| pressed) {
+                        Some(key) => self.v[x as usize] = key as u8,
+                        // No key down yet: rewind so this instruction is fetched again next cycle.
+                        None => self.program_counter -= 2,
+                    }
+                },
+                // LD Vx, DT
+                0x07 => self.v[x as usize] = self.delay_timer,
+                // LD DT, Vx
+                0x15 => self.delay_timer = self.v[x as usize],
+                // LD ST, Vx
+                0x18 => self.sound_timer = self.v[x as usize],
+                // ADD I, Vx
+                0x1E => self.index_register += self.v[x as usize] as u16,
+                // LD F, Vx
+                0x29 => self.index_register = (self.v[x as usize] as u16) * 5,
+                // LD B, Vx
+                0x33 => {
+                    let value = self.v[x as usize];
+                    self.memory[self.index_register as usize] = value / 100;
+                    self.memory[(self.index_register + 1) as usize] = (value / 10) % 10;
+                    self.memory[(self.index_register + 2) as usize] = value % 10;
+                },
+                // LD [I], Vx
+                0x55 => {
+                    for offset in 0..=(x as usize) {
+                        self.memory[(self.index_register as usize) + offset] = self.v[offset];
+                    }
+                    if self.quirks.load_store_increments_i {
+                        self.index_register += x + 1;
+                    }
+                },
+                // LD Vx, [I]
+                0x65 => {
+                    for offset in 0..=(x as usize) {
+                        self.v[offset] = self.memory[(self.index_register as usize) + offset];
+                    }
+                    if self.quirks.load_store_increments_i {
+                        self.index_register += x + 1;
+                    }
+                },
+                _ => eprint!("Invalid opcode 0x{:X}", self.current_opcode),
+            },
             _ => {
                 eprint!("Invalid opcode 0x{:X}", self.current_opcode);
             }
@@ -211,7 +383,7 @@ impl Chip8 {
     }
 
     /// Chip-8 also has two special purpose 8-bit registers, for the delay and sound timers. When these registers are non-zero, they are automatically decremented at a rate of 60Hz.
-    fn update_timers(&mut self) {
+    pub(crate) fn update_timers(&mut self) {
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
@@ -225,6 +397,209 @@ impl Chip8 {
     }
 
     fn clear_screen(&mut self) {
-        todo!()
+        self.gfx = [0; 64 * 32];
+    }
+}
+
+/// Accessors used by the optional GDB remote-serial-protocol debug stub.
+#[cfg(feature = "gdbstub")]
+impl Chip8 {
+    pub(crate) fn program_counter(&self) -> u16 {
+        self.program_counter
+    }
+
+    pub(crate) fn set_program_counter(&mut self, value: u16) {
+        self.program_counter = value;
+    }
+
+    pub(crate) fn registers(&self) -> [u8; 16] {
+        self.v
+    }
+
+    pub(crate) fn set_registers(&mut self, registers: [u8; 16]) {
+        self.v = registers;
+    }
+
+    pub(crate) fn index_register(&self) -> u16 {
+        self.index_register
+    }
+
+    pub(crate) fn set_index_register(&mut self, value: u16) {
+        self.index_register = value;
+    }
+
+    pub(crate) fn stack_pointer(&self) -> u16 {
+        self.stack_pointer
+    }
+
+    pub(crate) fn set_stack_pointer(&mut self, value: u16) {
+        self.stack_pointer = value;
+    }
+
+    pub(crate) fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub(crate) fn set_delay_timer(&mut self, value: u8) {
+        self.delay_timer = value;
+    }
+
+    pub(crate) fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    pub(crate) fn set_sound_timer(&mut self, value: u8) {
+        self.sound_timer = value;
+    }
+
+    pub(crate) fn memory(&self) -> &[u8; 4096] {
+        &self.memory
+    }
+
+    pub(crate) fn memory_mut(&mut self) -> &mut [u8; 4096] {
+        &mut self.memory
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chip8_with_opcode(opcode: u16) -> Chip8 {
+        let mut chip8 = Chip8::default();
+        let [high, low] = opcode.to_be_bytes();
+        chip8.memory[0x200] = high;
+        chip8.memory[0x201] = low;
+        chip8
+    }
+
+    #[test]
+    fn step_advances_pc_past_a_non_branching_instruction() {
+        // LD V0, 0x42
+        let mut chip8 = chip8_with_opcode(0x6042);
+        chip8.step();
+        assert_eq!(chip8.v[0], 0x42);
+        assert_eq!(chip8.program_counter, 0x202);
+    }
+
+    #[test]
+    fn jp_sets_pc_absolutely_instead_of_advancing_by_two() {
+        let mut chip8 = chip8_with_opcode(0x1300);
+        chip8.step();
+        assert_eq!(chip8.program_counter, 0x300);
+    }
+
+    #[test]
+    fn call_and_ret_round_trip_through_the_stack() {
+        let mut chip8 = chip8_with_opcode(0x2300);
+        chip8.step();
+        assert_eq!(chip8.program_counter, 0x300);
+        assert_eq!(chip8.stack[1], 0x202);
+
+        chip8.memory[0x300] = 0x00;
+        chip8.memory[0x301] = 0xEE;
+        chip8.step();
+        assert_eq!(chip8.program_counter, 0x202);
+    }
+
+    #[test]
+    fn skip_equal_advances_pc_by_four_when_taken() {
+        // SE V0, 0x42
+        let mut chip8 = chip8_with_opcode(0x3042);
+        chip8.v[0] = 0x42;
+        chip8.step();
+        assert_eq!(chip8.program_counter, 0x204);
+    }
+
+    #[test]
+    fn cls_zeroes_the_gfx_buffer_and_still_advances_pc() {
+        let mut chip8 = chip8_with_opcode(0x00E0);
+        chip8.gfx[0] = 0xFF;
+        chip8.step();
+        assert!(chip8.gfx.iter().all(|&pixel| pixel == 0));
+        assert_eq!(chip8.program_counter, 0x202);
+    }
+
+    #[test]
+    fn drw_xors_sprite_bits_and_flags_collisions() {
+        // DRW V0, V1, 1
+        let mut chip8 = chip8_with_opcode(0xD011);
+        chip8.index_register = 0x300;
+        chip8.memory[0x300] = 0xFF;
+
+        chip8.step();
+        assert_eq!(chip8.v[F], 0);
+        assert_eq!(chip8.gfx[0], 0xFF);
+
+        chip8.program_counter = 0x200;
+        chip8.step();
+        assert_eq!(chip8.v[F], 1);
+        assert_eq!(chip8.gfx[0], 0);
+    }
+
+    #[test]
+    fn sub_and_subn_write_the_difference_into_vx() {
+        // SUB V0, V1
+        let mut chip8 = chip8_with_opcode(0x8015);
+        chip8.v[0] = 10;
+        chip8.v[1] = 3;
+        chip8.step();
+        assert_eq!(chip8.v[0], 7);
+        assert_eq!(chip8.v[F], 1);
+
+        // SUBN V0, V1
+        let mut chip8 = chip8_with_opcode(0x8017);
+        chip8.v[0] = 3;
+        chip8.v[1] = 10;
+        chip8.step();
+        assert_eq!(chip8.v[0], 7);
+        assert_eq!(chip8.v[F], 1);
+    }
+
+    #[test]
+    fn skp_does_not_panic_on_an_out_of_range_key_register() {
+        // SKP V0
+        let mut chip8 = chip8_with_opcode(0xE09E);
+        chip8.v[0] = 200;
+        chip8.step();
+        assert_eq!(chip8.program_counter, 0x202);
+    }
+
+    #[test]
+    fn shift_quirk_picks_vx_or_vy_as_the_shift_source() {
+        // SHR V0, V1
+        let mut chip8 = chip8_with_opcode(0x8016);
+        chip8.quirks = Quirks::chip8();
+        chip8.v[0] = 0b0000_0001;
+        chip8.v[1] = 0b0000_0010;
+        chip8.step();
+        assert_eq!(chip8.v[0], 0b0000_0001);
+        assert_eq!(chip8.v[F], 0);
+
+        let mut chip8 = chip8_with_opcode(0x8016);
+        chip8.quirks = Quirks::super_chip();
+        chip8.v[0] = 0b0000_0001;
+        chip8.v[1] = 0b0000_0010;
+        chip8.step();
+        assert_eq!(chip8.v[0], 0);
+        assert_eq!(chip8.v[F], 1);
+    }
+
+    #[test]
+    fn jump_with_vx_quirk_changes_which_register_offsets_bnnn() {
+        // JP V0/Vx, 0x310
+        let mut chip8 = chip8_with_opcode(0xB310);
+        chip8.quirks.jump_with_vx = false;
+        chip8.v[0] = 1;
+        chip8.v[3] = 2;
+        chip8.step();
+        assert_eq!(chip8.program_counter, 0x311);
+
+        let mut chip8 = chip8_with_opcode(0xB310);
+        chip8.quirks.jump_with_vx = true;
+        chip8.v[0] = 1;
+        chip8.v[3] = 2;
+        chip8.step();
+        assert_eq!(chip8.program_counter, 0x312);
     }
 }
@@ -0,0 +1,112 @@
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::chip8::{Chip8, ProgramKind};
+#[cfg(feature = "gdbstub")]
+use crate::gdbstub::{DebugState, RunMode};
+
+/// How many instructions per second the interpreter thread targets.
+const INSTRUCTION_HZ: u64 = 600;
+const TIMER_HZ: u64 = 60;
+
+/// Commands the UI thread can send to the dedicated interpreter thread.
+pub(crate) enum Command {
+    Pause,
+    Resume,
+    Reset,
+    LoadRom(Vec<u8>, ProgramKind),
+}
+
+/// Spawns the CPU on its own thread, controlled through `command_rx`, and returns a handle to
+/// it and the `Chip8` state shared with the render/input thread.
+pub(crate) fn spawn(command_rx: Receiver<Command>) -> (JoinHandle<()>, Arc<Mutex<Chip8>>) {
+    let chip8 = Arc::new(Mutex::new(Chip8::default()));
+    let thread_chip8 = chip8.clone();
+
+    #[cfg(feature = "gdbstub")]
+    let handle = thread::spawn(move || run(thread_chip8, command_rx, None));
+    #[cfg(not(feature = "gdbstub"))]
+    let handle = thread::spawn(move || run(thread_chip8, command_rx));
+
+    (handle, chip8)
+}
+
+/// Same as [`spawn`], but the fetch/decode loop checks `debug`'s breakpoint set before each
+/// cycle and hands control back to the GDB stub when one is hit.
+#[cfg(feature = "gdbstub")]
+pub(crate) fn spawn_with_debug(
+    command_rx: Receiver<Command>,
+    debug: Arc<Mutex<DebugState>>,
+) -> (JoinHandle<()>, Arc<Mutex<Chip8>>) {
+    let chip8 = Arc::new(Mutex::new(Chip8::default()));
+    let thread_chip8 = chip8.clone();
+
+    let handle = thread::spawn(move || run(thread_chip8, command_rx, Some(debug)));
+
+    (handle, chip8)
+}
+
+#[cfg(feature = "gdbstub")]
+fn hit_breakpoint(chip8: &Arc<Mutex<Chip8>>, debug: &Arc<Mutex<DebugState>>) -> bool {
+    let pc = chip8.lock().unwrap().program_counter();
+    debug.lock().unwrap().breakpoints.contains(&pc)
+}
+
+fn run(chip8: Arc<Mutex<Chip8>>, command_rx: Receiver<Command>, #[cfg(feature = "gdbstub")] debug: Option<Arc<Mutex<DebugState>>>) {
+    let cycle_interval = Duration::from_secs_f64(1.0 / INSTRUCTION_HZ as f64);
+    let timer_interval = Duration::from_secs_f64(1.0 / TIMER_HZ as f64);
+
+    let mut paused = false;
+    let mut last_cycle = Instant::now();
+    let mut last_timer_tick = Instant::now();
+
+    loop {
+        match command_rx.try_recv() {
+            Ok(Command::Pause) => paused = true,
+            Ok(Command::Resume) => paused = false,
+            Ok(Command::Reset) => {
+                let mut chip8 = chip8.lock().unwrap();
+                let quirks = chip8.quirks;
+                *chip8 = Chip8::default();
+                chip8.quirks = quirks;
+            },
+            Ok(Command::LoadRom(rom, kind)) => {
+                let mut chip8 = chip8.lock().unwrap();
+                let quirks = chip8.quirks;
+                *chip8 = Chip8::default();
+                chip8.quirks = quirks;
+                chip8.load_program(&rom, kind);
+            },
+            Err(TryRecvError::Empty) => {},
+            Err(TryRecvError::Disconnected) => return,
+        }
+
+        #[cfg(feature = "gdbstub")]
+        if let Some(debug) = &debug {
+            if debug.lock().unwrap().mode == RunMode::Stopped {
+                thread::sleep(Duration::from_millis(1));
+                continue;
+            }
+            if hit_breakpoint(&chip8, debug) {
+                debug.lock().unwrap().mode = RunMode::Stopped;
+                continue;
+            }
+        }
+
+        let now = Instant::now();
+
+        if !paused && now.duration_since(last_cycle) >= cycle_interval {
+            chip8.lock().unwrap().step();
+            last_cycle = now;
+        }
+
+        if now.duration_since(last_timer_tick) >= timer_interval {
+            chip8.lock().unwrap().update_timers();
+            last_timer_tick = now;
+        }
+
+        thread::sleep(Duration::from_millis(1));
+    }
+}
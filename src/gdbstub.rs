@@ -0,0 +1,217 @@
+//! A minimal GDB Remote Serial Protocol stub for the CHIP-8 core, so a debugger can attach
+//! over TCP to inspect registers/memory and single-step or run to a breakpoint.
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::chip8::Chip8;
+
+#[derive(PartialEq, Eq)]
+pub(crate) enum RunMode {
+    Running,
+    Stopped,
+}
+
+pub(crate) struct DebugState {
+    pub(crate) breakpoints: HashSet<u16>,
+    pub(crate) mode: RunMode,
+}
+
+impl Default for DebugState {
+    fn default() -> DebugState {
+        DebugState {
+            breakpoints: HashSet::new(),
+            mode: RunMode::Stopped,
+        }
+    }
+}
+
+/// Accepts debugger connections on `addr` and serves them one at a time.
+pub(crate) fn listen(addr: &str, chip8: Arc<Mutex<Chip8>>, debug: Arc<Mutex<DebugState>>) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    for stream in listener.incoming() {
+        serve(stream?, chip8.clone(), debug.clone());
+    }
+    Ok(())
+}
+
+fn serve(mut stream: TcpStream, chip8: Arc<Mutex<Chip8>>, debug: Arc<Mutex<DebugState>>) {
+    while let Some(payload) = read_packet(&mut stream) {
+        let _ = stream.write_all(b"+");
+        let response = handle_packet(&payload, &chip8, &debug);
+        send_packet(&mut stream, &response);
+    }
+}
+
+/// Reads one `$<payload>#<checksum>` frame, verifying the checksum. Returns `None` on EOF.
+fn read_packet(stream: &mut TcpStream) -> Option<String> {
+    let mut byte = [0u8; 1];
+
+    loop {
+        if stream.read(&mut byte).ok()? == 0 {
+            return None;
+        }
+        if byte[0] == b'$' {
+            break;
+        }
+    }
+
+    let mut payload = Vec::new();
+    loop {
+        if stream.read(&mut byte).ok()? == 0 {
+            return None;
+        }
+        if byte[0] == b'#' {
+            break;
+        }
+        payload.push(byte[0]);
+    }
+
+    let mut checksum_hex = [0u8; 2];
+    stream.read_exact(&mut checksum_hex).ok()?;
+    let expected = u8::from_str_radix(std::str::from_utf8(&checksum_hex).ok()?, 16).ok()?;
+    let actual = payload.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+
+    if actual != expected {
+        let _ = stream.write_all(b"-");
+        return read_packet(stream);
+    }
+
+    String::from_utf8(payload).ok()
+}
+
+fn send_packet(stream: &mut TcpStream, payload: &str) {
+    let checksum = payload.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+    let _ = write!(stream, "${payload}#{checksum:02x}");
+}
+
+fn handle_packet(payload: &str, chip8: &Arc<Mutex<Chip8>>, debug: &Arc<Mutex<DebugState>>) -> String {
+    match payload.as_bytes().first() {
+        Some(b'g') => read_registers(chip8),
+        Some(b'G') => write_registers(chip8, &payload[1..]),
+        Some(b'm') => read_memory(chip8, &payload[1..]),
+        Some(b'M') => write_memory(chip8, &payload[1..]),
+        Some(b'c') => continue_execution(chip8, debug),
+        Some(b's') => single_step(chip8),
+        Some(b'Z') if payload.starts_with("Z0,") => set_breakpoint(debug, &payload[3..], true),
+        Some(b'z') if payload.starts_with("z0,") => set_breakpoint(debug, &payload[3..], false),
+        _ => String::new(),
+    }
+}
+
+fn read_registers(chip8: &Arc<Mutex<Chip8>>) -> String {
+    let chip8 = chip8.lock().unwrap();
+    let mut hex = String::new();
+
+    for byte in chip8.registers() {
+        hex.push_str(&format!("{byte:02x}"));
+    }
+    for word in [chip8.index_register(), chip8.program_counter(), chip8.stack_pointer()] {
+        hex.push_str(&format!("{:02x}{:02x}", word as u8, (word >> 8) as u8));
+    }
+    hex.push_str(&format!("{:02x}{:02x}", chip8.delay_timer(), chip8.sound_timer()));
+
+    hex
+}
+
+fn write_registers(chip8: &Arc<Mutex<Chip8>>, hex: &str) -> String {
+    let Some(bytes) = decode_hex(hex) else { return "E01".to_string() };
+    if bytes.len() != 24 {
+        return "E01".to_string();
+    }
+
+    let mut registers = [0u8; 16];
+    registers.copy_from_slice(&bytes[0..16]);
+
+    let mut chip8 = chip8.lock().unwrap();
+    chip8.set_registers(registers);
+    chip8.set_index_register(u16::from_le_bytes([bytes[16], bytes[17]]));
+    chip8.set_program_counter(u16::from_le_bytes([bytes[18], bytes[19]]));
+    chip8.set_stack_pointer(u16::from_le_bytes([bytes[20], bytes[21]]));
+    chip8.set_delay_timer(bytes[22]);
+    chip8.set_sound_timer(bytes[23]);
+
+    "OK".to_string()
+}
+
+fn read_memory(chip8: &Arc<Mutex<Chip8>>, args: &str) -> String {
+    let Some((addr, len)) = parse_addr_len(args) else { return "E01".to_string() };
+    let chip8 = chip8.lock().unwrap();
+    let memory = chip8.memory();
+
+    let Some(end) = addr.checked_add(len) else { return "E01".to_string() };
+    let Some(slice) = memory.get(addr..end) else { return "E01".to_string() };
+    slice.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn write_memory(chip8: &Arc<Mutex<Chip8>>, args: &str) -> String {
+    let Some((header, data)) = args.split_once(':') else { return "E01".to_string() };
+    let Some((addr, len)) = parse_addr_len(header) else { return "E01".to_string() };
+    let Some(bytes) = decode_hex(data) else { return "E01".to_string() };
+    if bytes.len() != len {
+        return "E01".to_string();
+    }
+
+    let mut chip8 = chip8.lock().unwrap();
+    let memory = chip8.memory_mut();
+    let Some(end) = addr.checked_add(len) else { return "E01".to_string() };
+    if end > memory.len() {
+        return "E01".to_string();
+    }
+    memory[addr..end].copy_from_slice(&bytes);
+
+    "OK".to_string()
+}
+
+fn continue_execution(chip8: &Arc<Mutex<Chip8>>, debug: &Arc<Mutex<DebugState>>) -> String {
+    debug.lock().unwrap().mode = RunMode::Running;
+
+    loop {
+        if debug.lock().unwrap().mode == RunMode::Stopped {
+            break;
+        }
+        let _ = chip8;
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    "S05".to_string()
+}
+
+fn single_step(chip8: &Arc<Mutex<Chip8>>) -> String {
+    chip8.lock().unwrap().step();
+    "S05".to_string()
+}
+
+fn set_breakpoint(debug: &Arc<Mutex<DebugState>>, args: &str, insert: bool) -> String {
+    let Some((addr_hex, _kind)) = args.split_once(',') else { return "E01".to_string() };
+    let Ok(addr) = u16::from_str_radix(addr_hex, 16) else { return "E01".to_string() };
+
+    let mut debug = debug.lock().unwrap();
+    if insert {
+        debug.breakpoints.insert(addr);
+    } else {
+        debug.breakpoints.remove(&addr);
+    }
+
+    "OK".to_string()
+}
+
+fn parse_addr_len(args: &str) -> Option<(usize, usize)> {
+    let (addr_hex, len_hex) = args.split_once(',')?;
+    let addr = usize::from_str_radix(addr_hex, 16).ok()?;
+    let len = usize::from_str_radix(len_hex, 16).ok()?;
+    Some((addr, len))
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
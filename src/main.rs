@@ -1,19 +1,72 @@
-use std::sync::Arc;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use pixels::{Pixels, SurfaceTexture};
 use pixels::wgpu::PresentMode;
 use winit::application::ApplicationHandler;
 use winit::dpi::LogicalSize;
-use winit::event::WindowEvent;
+use winit::event::{ElementState, WindowEvent};
 use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
+use winit::keyboard::{KeyCode, PhysicalKey};
 use winit::window::{Window, WindowId};
 
 mod chip8;
+mod emulator;
+#[cfg(feature = "gdbstub")]
+mod gdbstub;
+mod tty;
+
+use chip8::{Chip8, ProgramKind, Quirks};
+use emulator::Command;
+
+/// Address the optional GDB remote-serial-protocol stub listens on.
+#[cfg(feature = "gdbstub")]
+const GDB_STUB_ADDR: &str = "127.0.0.1:9999";
+
+/// Spawns the interpreter thread (and the GDB stub, if enabled) shared by every render backend.
+fn spawn_emulator() -> (Arc<Mutex<Chip8>>, Sender<Command>) {
+    let (command_tx, command_rx) = mpsc::channel();
+
+    #[cfg(feature = "gdbstub")]
+    let (_cpu_thread, chip8) = {
+        let debug = Arc::new(Mutex::new(gdbstub::DebugState::default()));
+        let (handle, chip8) = emulator::spawn_with_debug(command_rx, debug.clone());
+
+        let stub_chip8 = chip8.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = gdbstub::listen(GDB_STUB_ADDR, stub_chip8, debug) {
+                eprintln!("gdbstub: {e}");
+            }
+        });
+
+        (handle, chip8)
+    };
+
+    #[cfg(not(feature = "gdbstub"))]
+    let (_cpu_thread, chip8) = emulator::spawn(command_rx);
+
+    (chip8, command_tx)
+}
 
 const WIDTH: u32 = 64;
 const HEIGHT: u32 = 32;
 const FRAME_TIME: Duration = Duration::from_millis(1000 / 240);
 
+/// RGBA foreground/background colors the `gfx` buffer is painted with.
+struct Palette {
+    foreground: [u8; 4],
+    background: [u8; 4],
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette {
+            foreground: [0x00, 0xF0, 0x00, 0xFF],
+            background: [0x00, 0x00, 0x00, 0xFF],
+        }
+    }
+}
+
 struct FPSDebug {
     last_frame: Instant,
     last_fps_check: Instant,
@@ -30,11 +83,52 @@ impl Default for FPSDebug {
     }
 }
 
-#[derive(Default)]
+/// Maps the conventional 4x4 CHIP-8 keypad layout onto the left-hand side of a QWERTY
+/// keyboard: `1234`/`QWER`/`ASDF`/`ZXCV` become CHIP-8 keys `123C`/`456D`/`789E`/`A0BF`.
+fn map_key_to_chip8(key_code: KeyCode) -> Option<usize> {
+    match key_code {
+        KeyCode::Digit1 => Some(0x1),
+        KeyCode::Digit2 => Some(0x2),
+        KeyCode::Digit3 => Some(0x3),
+        KeyCode::Digit4 => Some(0xC),
+        KeyCode::KeyQ => Some(0x4),
+        KeyCode::KeyW => Some(0x5),
+        KeyCode::KeyE => Some(0x6),
+        KeyCode::KeyR => Some(0xD),
+        KeyCode::KeyA => Some(0x7),
+        KeyCode::KeyS => Some(0x8),
+        KeyCode::KeyD => Some(0x9),
+        KeyCode::KeyF => Some(0xE),
+        KeyCode::KeyZ => Some(0xA),
+        KeyCode::KeyX => Some(0x0),
+        KeyCode::KeyC => Some(0xB),
+        KeyCode::KeyV => Some(0xF),
+        _ => None,
+    }
+}
+
 struct App {
     window: Option<Arc<Window>>,
     pixels: Option<Pixels<'static>>,
-    fps_debug: FPSDebug
+    fps_debug: FPSDebug,
+    chip8: Arc<Mutex<Chip8>>,
+    commands: Sender<Command>,
+    palette: Palette,
+}
+
+impl App {
+    fn new(palette: Palette) -> App {
+        let (chip8, commands) = spawn_emulator();
+
+        App {
+            window: None,
+            pixels: None,
+            fps_debug: FPSDebug::default(),
+            chip8,
+            commands,
+            palette,
+        }
+    }
 }
 
 impl ApplicationHandler for App {
@@ -71,6 +165,26 @@ impl ApplicationHandler for App {
                 println!("Close Requested");
                 event_loop.exit();
             },
+            WindowEvent::KeyboardInput { event, .. } => {
+                if let PhysicalKey::Code(key_code) = event.physical_key {
+                    match key_code {
+                        KeyCode::F1 if event.state == ElementState::Pressed => {
+                            let _ = self.commands.send(Command::Pause);
+                        },
+                        KeyCode::F2 if event.state == ElementState::Pressed => {
+                            let _ = self.commands.send(Command::Resume);
+                        },
+                        KeyCode::F3 if event.state == ElementState::Pressed => {
+                            let _ = self.commands.send(Command::Reset);
+                        },
+                        _ => {
+                            if let Some(chip8_key) = map_key_to_chip8(key_code) {
+                                self.chip8.lock().unwrap().keys[chip8_key] = event.state == ElementState::Pressed;
+                            }
+                        }
+                    }
+                }
+            },
             WindowEvent::RedrawRequested => {
                 let now = Instant::now();
                 let frame_time = now.duration_since(self.fps_debug.last_frame);
@@ -78,8 +192,10 @@ impl ApplicationHandler for App {
 
                 if let Some(pixels) = self.pixels.as_mut() {
                     let frame = pixels.frame_mut();
-                    for chunk in frame.chunks_exact_mut(4) {
-                        chunk.copy_from_slice(&[0x00, 0xF0, 0x00, 0xFF]); // black
+                    let gfx = self.chip8.lock().unwrap().gfx;
+                    for (pixel, chunk) in gfx.iter().zip(frame.chunks_exact_mut(4)) {
+                        let color = if *pixel != 0 { self.palette.foreground } else { self.palette.background };
+                        chunk.copy_from_slice(&color);
                     }
 
                     if let Err(e) = pixels.render() {
@@ -111,10 +227,87 @@ impl ApplicationHandler for App {
     }
 }
 
-fn main() -> Result<(), impl std::error::Error> {
+/// Parses a `RRGGBB` hex string into an opaque RGBA color.
+fn parse_color(hex: &str) -> Option<[u8; 4]> {
+    let r = u8::from_str_radix(hex.get(0..2)?, 16).ok()?;
+    let g = u8::from_str_radix(hex.get(2..4)?, 16).ok()?;
+    let b = u8::from_str_radix(hex.get(4..6)?, 16).ok()?;
+    Some([r, g, b, 0xFF])
+}
+
+/// Parsed command-line arguments:
+/// `[--tty] [--quirks classic|super-chip] [--foreground RRGGBB] [--background RRGGBB] [rom_path]`.
+struct CliArgs {
+    tty: bool,
+    quirks: Quirks,
+    palette: Palette,
+    rom_path: Option<String>,
+}
+
+fn parse_args() -> CliArgs {
+    let mut tty = false;
+    let mut quirks = Quirks::chip8();
+    let mut palette = Palette::default();
+    let mut rom_path = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--tty" => tty = true,
+            "--quirks" => {
+                quirks = match args.next().as_deref() {
+                    Some("super-chip") => Quirks::super_chip(),
+                    Some("classic") => Quirks::chip8(),
+                    other => {
+                        eprintln!("unknown --quirks value {other:?}, defaulting to classic");
+                        Quirks::chip8()
+                    }
+                };
+            },
+            "--foreground" => match args.next().as_deref().and_then(parse_color) {
+                Some(color) => palette.foreground = color,
+                None => eprintln!("--foreground expects a RRGGBB hex value"),
+            },
+            "--background" => match args.next().as_deref().and_then(parse_color) {
+                Some(color) => palette.background = color,
+                None => eprintln!("--background expects a RRGGBB hex value"),
+            },
+            _ => rom_path = Some(arg),
+        }
+    }
+
+    CliArgs { tty, quirks, palette, rom_path }
+}
+
+fn load_rom(commands: &Sender<Command>, path: &str) {
+    match std::fs::read(path) {
+        Ok(rom) => {
+            let _ = commands.send(Command::LoadRom(rom, ProgramKind::CHIP8));
+        },
+        Err(e) => eprintln!("couldn't read ROM {path}: {e}"),
+    }
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = parse_args();
+
+    if args.tty {
+        let (chip8, commands) = spawn_emulator();
+        chip8.lock().unwrap().quirks = args.quirks;
+        if let Some(path) = &args.rom_path {
+            load_rom(&commands, path);
+        }
+        tty::run(chip8);
+    }
+
     let event_loop = EventLoop::new()?;
     event_loop.set_control_flow(ControlFlow::Poll);
 
-    let mut app = App::default();
-    event_loop.run_app(&mut app)
+    let mut app = App::new(args.palette);
+    app.chip8.lock().unwrap().quirks = args.quirks;
+    if let Some(path) = &args.rom_path {
+        load_rom(&app.commands, path);
+    }
+    event_loop.run_app(&mut app)?;
+    Ok(())
 }
@@ -0,0 +1,42 @@
+//! A headless rendering backend that draws the `gfx` buffer straight to a terminal instead of
+//! opening a window, useful over SSH or for a quick CI smoke test.
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::chip8::Chip8;
+
+const FRAME_TIME: Duration = Duration::from_millis(1000 / 60);
+/// Move the cursor to the top-left corner instead of scrolling the terminal every frame.
+const CURSOR_HOME: &str = "\x1B[H";
+
+/// Renders `chip8`'s display to stdout until the process is killed. Each pair of CHIP-8 pixel
+/// rows is packed into one row of Unicode half-block glyphs, so the 32-row display fits in 16
+/// lines of text.
+pub(crate) fn run(chip8: Arc<Mutex<Chip8>>) -> ! {
+    let mut stdout = std::io::stdout();
+
+    loop {
+        let gfx = chip8.lock().unwrap().gfx;
+        let mut frame = String::from(CURSOR_HOME);
+
+        for row_pair in gfx.chunks(64 * 2) {
+            let (top, bottom) = row_pair.split_at(64);
+            for (top_pixel, bottom_pixel) in top.iter().zip(bottom) {
+                frame.push(match (*top_pixel != 0, *bottom_pixel != 0) {
+                    (false, false) => ' ',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (true, true) => '█',
+                });
+            }
+            frame.push('\n');
+        }
+
+        let _ = stdout.write_all(frame.as_bytes());
+        let _ = stdout.flush();
+
+        thread::sleep(FRAME_TIME);
+    }
+}